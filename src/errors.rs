@@ -0,0 +1,44 @@
+//! Error chain definitions for this crate.
+
+use hyper;
+
+error_chain! {
+    foreign_links {
+        Hyper(hyper::Error);
+        Uri(hyper::error::UriError);
+        Json(::serde_json::Error);
+        Io(::std::io::Error);
+    }
+
+    errors {
+        /// The registry returned an HTTP status we don't know how to handle.
+        UnexpectedHttpStatus(status: hyper::StatusCode) {
+            description("unexpected HTTP status")
+            display("unexpected HTTP status: {}", status)
+        }
+
+        /// The registry challenged us for auth but the `WWW-Authenticate`
+        /// header was missing or could not be parsed.
+        MissingAuthChallenge {
+            description("missing or unparsable WWW-Authenticate header")
+        }
+
+        /// A digest computed over content received from the registry did
+        /// not match what the caller expected.
+        DigestMismatch(expected: String, actual: String) {
+            description("digest mismatch")
+            display("digest mismatch: expected {}, got {}", expected, actual)
+        }
+
+        /// The registry refused a deletion request (HTTP 405), i.e. it
+        /// does not support deletion of manifests/blobs.
+        DeleteUnsupported {
+            description("registry does not support deletion of this resource")
+        }
+
+        /// The requested manifest/blob does not exist on the registry.
+        NotFound {
+            description("resource not found")
+        }
+    }
+}
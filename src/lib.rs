@@ -0,0 +1,40 @@
+//! `dkregistry` is a pure-Rust asynchronous library for Docker Registry API v2.
+//!
+//! It provides support for:
+//!
+//! * Querying for images and listing their tags.
+//! * Checking for image manifest existence, fetching and parsing it.
+//! * Checking for image blob existence, fetching its content.
+//!
+//! ```rust,no_run
+//! extern crate dkregistry;
+//! extern crate tokio_core;
+//!
+//! fn main() {
+//!     let mut tcore = tokio_core::reactor::Core::new().unwrap();
+//!     let dclient = dkregistry::v2::Client::configure(&tcore.handle())
+//!         .registry("quay.io")
+//!         .build()
+//!         .unwrap();
+//! }
+//! ```
+
+#![deny(missing_debug_implementations)]
+
+extern crate futures;
+extern crate hyper;
+extern crate hyper_rustls;
+#[macro_use]
+extern crate error_chain;
+#[macro_use]
+extern crate log;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate sha2;
+extern crate tokio_core;
+extern crate ttl_cache;
+
+pub mod errors;
+pub mod v2;
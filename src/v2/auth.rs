@@ -0,0 +1,274 @@
+use futures::Future;
+use hyper;
+use hyper::client;
+use hyper::header::{Authorization, Basic};
+use serde_json;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::super::errors::*;
+use super::{Client, DEFAULT_TOKEN_TTL_SECS};
+
+/// A bearer token obtained from a registry's token server, together with
+/// the scope it was issued for and the lifetime it was issued with.
+#[derive(Debug, Clone, Default)]
+pub struct TokenAuth {
+    pub token: String,
+    pub scope: String,
+    pub expires_in: u64,
+}
+
+/// Convenience alias for a future bearer token.
+pub type FutureTokenAuth = Box<Future<Item = TokenAuth, Error = Error>>;
+
+/// A parsed `WWW-Authenticate: Bearer ...` challenge, as sent by a
+/// registry in response to an unauthenticated request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Challenge {
+    pub realm: String,
+    pub service: String,
+    pub scope: String,
+}
+
+impl Challenge {
+    /// Parse a `WWW-Authenticate` header value of the form
+    /// `Bearer realm="...",service="...",scope="..."`.
+    pub fn parse(header: &str) -> Result<Self> {
+        let header = header.trim();
+        if !header.starts_with("Bearer ") {
+            return Err(ErrorKind::MissingAuthChallenge.into());
+        }
+
+        let mut realm = None;
+        let mut service = None;
+        let mut scope = None;
+        for param in split_unquoted(&header["Bearer ".len()..], ',') {
+            let mut kv = param.splitn(2, '=');
+            let key = kv.next().unwrap_or("").trim();
+            let value = kv.next().unwrap_or("").trim().trim_matches('"');
+            match key {
+                "realm" => realm = Some(value.to_string()),
+                "service" => service = Some(value.to_string()),
+                "scope" => scope = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        Ok(Challenge {
+            realm: realm.ok_or(ErrorKind::MissingAuthChallenge)?,
+            service: service.unwrap_or_default(),
+            scope: scope.unwrap_or_default(),
+        })
+    }
+
+    /// The individual actions requested by this challenge's `scope`
+    /// parameter (e.g. `["pull", "push"]` for
+    /// `repository:coreos/etcd:pull,push`), so a caller can decide
+    /// whether to ask for a narrower or wider scope explicitly.
+    pub fn actions(&self) -> Vec<String> {
+        match self.scope.rsplit(':').next() {
+            Some(actions) if !actions.is_empty() => {
+                actions.split(',').map(|a| a.to_string()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Split `s` on `sep`, ignoring any `sep` that falls inside a pair of
+/// double quotes (as happens with `scope="repository:foo:pull,push"`,
+/// where the comma is part of the value, not a parameter separator).
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, c) in s.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == sep && !in_quotes {
+            parts.push(s[start..i].trim());
+            start = i + 1;
+        }
+    }
+    parts.push(s[start..].trim());
+    parts
+}
+
+/// Percent-encode the handful of characters that show up in registry
+/// scopes/services and are not safe to place verbatim in a query string.
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'_' | b'.' | b'~' | b':' | b'/' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn strip_query(uri: &str) -> &str {
+    match uri.find('?') {
+        Some(idx) => &uri[..idx],
+        None => uri,
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+impl Client {
+    /// Perform the bearer-token handshake against a previously-parsed
+    /// challenge, requesting the given `scope` (e.g.
+    /// `repository:coreos/etcd:pull`).
+    ///
+    /// The token server URI is built from the challenge's `realm`, not
+    /// from this client's `base_url` -- the two may live on entirely
+    /// different hosts.
+    pub(crate) fn authenticate(&self, challenge: &Challenge, scope: &str) -> Result<FutureTokenAuth> {
+        let query = format!(
+            "service={}&scope={}",
+            percent_encode(&challenge.service),
+            percent_encode(scope),
+        );
+        let base = strip_query(&challenge.realm);
+        let realm = hyper::Uri::from_str(&match challenge.realm.find('?') {
+            Some(idx) => format!("{}?{}&{}", base, &challenge.realm[idx + 1..], query),
+            None => format!("{}?{}", base, query),
+        })?;
+
+        let mut req = client::Request::new(hyper::Method::Get, realm.clone());
+        if let Some((ref user, ref pass)) = self.credentials {
+            req.headers_mut().set(Authorization(Basic {
+                username: user.to_owned(),
+                password: Some(pass.to_owned()),
+            }));
+        }
+        if let Some(ref ua) = self.user_agent {
+            req.headers_mut()
+                .set(hyper::header::UserAgent::new(ua.to_owned()));
+        }
+
+        let scope = scope.to_string();
+        let fres = self
+            .hclient
+            .request(req)
+            .map(move |r| {
+                trace!("GET {:?}", realm);
+                r
+            }).from_err()
+            .and_then(|r| match r.status() {
+                hyper::StatusCode::Ok => Ok(r),
+                s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+            }).and_then(|r| r.body().concat2().from_err())
+            .and_then(move |chunk| {
+                let parsed: TokenResponse = serde_json::from_slice(&chunk)?;
+                let token = parsed
+                    .token
+                    .or(parsed.access_token)
+                    .ok_or_else(|| Error::from("token response had neither `token` nor `access_token`"))?;
+                let expires_in = parsed.expires_in.unwrap_or(DEFAULT_TOKEN_TTL_SECS);
+                Ok(TokenAuth {
+                    token,
+                    scope,
+                    expires_in,
+                })
+            });
+        Ok(Box::new(fres))
+    }
+
+    /// Look up a still-valid cached token for `scope`, if any.
+    pub(crate) fn cached_token(&self, scope: &str) -> Option<String> {
+        self.token_cache.borrow_mut().get(scope).cloned()
+    }
+
+    /// Cache a freshly obtained token under its scope, evicting it once
+    /// its `expires_in` lifetime has elapsed.
+    pub(crate) fn cache_token(&self, auth: &TokenAuth) {
+        self.token_cache.borrow_mut().insert(
+            auth.scope.clone(),
+            auth.token.clone(),
+            Duration::from_secs(auth.expires_in),
+        );
+    }
+
+    /// Probe the registry for the bearer-token actions it advertises for
+    /// repository `name` (e.g. `["pull", "push"]`), by issuing an
+    /// unauthenticated request and parsing its `401` challenge.
+    ///
+    /// This lets a caller request `pull`/`push` scopes explicitly (by
+    /// building e.g. `repository:<name>:push` and passing it to calls
+    /// that accept a scope) instead of settling for whatever scope a
+    /// single endpoint happens to challenge for.
+    pub fn discover_scopes(&self, name: &str) -> Result<Box<Future<Item = Vec<String>, Error = Error>>> {
+        let url = hyper::Uri::from_str(&format!("{}/v2/{}/manifests/", self.base_url, name))?;
+        let req = self.new_request(hyper::Method::Get, url);
+        let fres = self
+            .hclient
+            .request(req)
+            .from_err()
+            .and_then(|res| match res.status() {
+                hyper::StatusCode::Unauthorized => {
+                    let challenge = res
+                        .headers()
+                        .get_raw("WWW-Authenticate")
+                        .and_then(|raw| raw.one())
+                        .ok_or_else(|| Error::from(ErrorKind::MissingAuthChallenge))
+                        .and_then(|bytes| Challenge::parse(&String::from_utf8_lossy(bytes)))?;
+                    Ok(challenge.actions())
+                }
+                _ => Ok(Vec::new()),
+            });
+        Ok(Box::new(fres))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Challenge;
+
+    #[test]
+    fn parse_single_scope() {
+        let c = Challenge::parse(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:coreos/etcd:pull""#,
+        ).unwrap();
+        assert_eq!(c.realm, "https://auth.docker.io/token");
+        assert_eq!(c.service, "registry.docker.io");
+        assert_eq!(c.scope, "repository:coreos/etcd:pull");
+        assert_eq!(c.actions(), vec!["pull"]);
+    }
+
+    #[test]
+    fn parse_multi_action_scope_with_embedded_comma() {
+        let c = Challenge::parse(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:coreos/etcd:pull,push""#,
+        ).unwrap();
+        assert_eq!(c.scope, "repository:coreos/etcd:pull,push");
+        assert_eq!(c.actions(), vec!["pull", "push"]);
+    }
+
+    #[test]
+    fn parse_params_in_any_order() {
+        let c = Challenge::parse(
+            r#"Bearer scope="registry:catalog:*",realm="https://auth.docker.io/token",service="registry.docker.io""#,
+        ).unwrap();
+        assert_eq!(c.realm, "https://auth.docker.io/token");
+        assert_eq!(c.scope, "registry:catalog:*");
+    }
+
+    #[test]
+    fn parse_missing_realm_is_an_error() {
+        assert!(Challenge::parse(r#"Bearer service="registry.docker.io""#).is_err());
+    }
+
+    #[test]
+    fn parse_rejects_non_bearer_schemes() {
+        assert!(Challenge::parse(r#"Basic realm="registry""#).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use futures;
+use futures::{Future, Stream};
+use hyper;
+use hyper::header::{ContentLength, ContentType, Location};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::super::errors::*;
+use super::Client;
+
+/// Convenience alias for future blob content.
+pub type FutureBlob = Box<Future<Item = Vec<u8>, Error = Error>>;
+
+/// Convenience alias for a blob body streamed as a series of chunks.
+pub type BlobStream = Box<Stream<Item = hyper::Chunk, Error = Error>>;
+
+/// A blob upload session, as handed out by `start_upload`.
+///
+/// `location` is the absolute URL to `PATCH`/`PUT` against, and `uuid` is
+/// the upload session identifier the registry assigned to it.
+#[derive(Debug, Clone)]
+pub struct Upload {
+    pub location: String,
+    pub uuid: String,
+}
+
+/// Convenience alias for a future upload session.
+pub type FutureUpload = Box<Future<Item = Upload, Error = Error>>;
+
+impl Client {
+    /// Check if a blob exists, by digest.
+    pub fn has_blob(&self, name: &str, digest: &str) -> Result<Box<Future<Item = bool, Error = Error>>> {
+        let url = hyper::Uri::from_str(
+            &format!("{}/v2/{}/blobs/{}", self.base_url, name, digest),
+        )?;
+        let scope = format!("repository:{}:pull", name);
+        let fres = self
+            .send_authed(&scope, move |c| c.new_request(hyper::Method::Head, url.clone()))
+            .map(|r| r.status() == hyper::StatusCode::Ok);
+        Ok(Box::new(fres))
+    }
+
+    /// Retrieve a blob, by digest, buffering the whole content in memory.
+    pub fn get_blob(&self, name: &str, digest: &str) -> Result<FutureBlob> {
+        let url = hyper::Uri::from_str(
+            &format!("{}/v2/{}/blobs/{}", self.base_url, name, digest),
+        )?;
+        let scope = format!("repository:{}:pull", name);
+        let fres = self
+            .send_authed(&scope, move |c| c.new_request(hyper::Method::Get, url.clone()))
+            .and_then(|r| match r.status() {
+                hyper::StatusCode::Ok => Ok(r),
+                s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+            }).and_then(|r| r.body().concat2().from_err())
+            .map(|chunk| chunk.to_vec());
+        Ok(Box::new(fres))
+    }
+
+    /// Stream a blob's content as a series of chunks, without buffering
+    /// the whole body in memory -- useful for multi-hundred-MB layers.
+    pub fn get_blob_stream(&self, name: &str, digest: &str) -> Result<BlobStream> {
+        let url = hyper::Uri::from_str(
+            &format!("{}/v2/{}/blobs/{}", self.base_url, name, digest),
+        )?;
+        let scope = format!("repository:{}:pull", name);
+        let fres = self
+            .send_authed(&scope, move |c| c.new_request(hyper::Method::Get, url.clone()))
+            .and_then(|r| match r.status() {
+                hyper::StatusCode::Ok => Ok(r.body().from_err()),
+                s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+            });
+        Ok(Box::new(fres.flatten_stream()))
+    }
+
+    /// Download a blob into `writer`, verifying its sha256 `digest`
+    /// incrementally as chunks arrive rather than buffering the whole
+    /// blob in memory first.
+    pub fn download_blob_to<W>(
+        &self,
+        name: &str,
+        digest: &str,
+        writer: W,
+    ) -> Result<Box<Future<Item = (), Error = Error>>>
+    where
+        W: Write + 'static,
+    {
+        let expected = digest.to_string();
+        let fres = self
+            .get_blob_stream(name, digest)?
+            .fold((writer, Sha256::new()), |(mut writer, mut hasher), chunk| {
+                futures::future::result(writer.write_all(&chunk).map_err(Error::from)).map(
+                    move |_| {
+                        hasher.input(&chunk);
+                        (writer, hasher)
+                    },
+                )
+            }).and_then(move |(_, hasher)| {
+                let actual = format!("sha256:{:x}", hasher.result());
+                verify_digest(&expected, &actual)
+            });
+        Ok(Box::new(fres))
+    }
+
+    /// Start a new blob upload session for a repository.
+    ///
+    /// On success, returns the `Location` to `PATCH`/`PUT` against for the
+    /// remainder of the upload.
+    pub fn start_upload(&self, name: &str) -> Result<FutureUpload> {
+        let url = hyper::Uri::from_str(
+            &format!("{}/v2/{}/blobs/uploads/", self.base_url, name),
+        )?;
+        let scope = format!("repository:{}:push", name);
+        let fres = self
+            .send_authed(&scope, move |c| c.new_request(hyper::Method::Post, url.clone()))
+            .and_then(|r| match r.status() {
+                hyper::StatusCode::Accepted => {
+                    let location = r
+                        .headers()
+                        .get::<Location>()
+                        .map(|l| l.to_string())
+                        .ok_or_else(|| Error::from("missing Location header in upload response"))?;
+                    let uuid = r
+                        .headers()
+                        .get_raw("Docker-Upload-UUID")
+                        .and_then(|raw| raw.one())
+                        .map(|bytes| String::from_utf8_lossy(bytes).into_owned());
+                    Ok((location, uuid))
+                }
+                s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+            }).and_then(|(location, uuid)| {
+                let uuid = uuid.unwrap_or_else(|| uuid_from_location(&location));
+                Ok(Upload { location, uuid })
+            });
+        Ok(Box::new(fres))
+    }
+
+    /// Upload one chunk of blob data at `offset`, against a session
+    /// previously obtained from `start_upload`.
+    ///
+    /// Returns the `Location` to continue the upload from on the next
+    /// chunk (registries are free to rewrite it on every `PATCH`).
+    pub fn patch_blob_chunk(
+        &self,
+        upload_url: &str,
+        offset: u64,
+        chunk: Vec<u8>,
+    ) -> Result<Box<Future<Item = String, Error = Error>>> {
+        let url = hyper::Uri::from_str(upload_url)?;
+        let scope = repo_name_from_upload_path(url.path())
+            .map(|name| format!("repository:{}:push", name))
+            .unwrap_or_default();
+        let len = chunk.len() as u64;
+        // Per the distribution spec, this is a bare `<start>-<end>` value,
+        // not hyper's typed `bytes <start>-<end>/*` Content-Range.
+        let range = format!("{}-{}", offset, offset + len.saturating_sub(1));
+        let chunk = Arc::new(chunk);
+        let fres = self
+            .send_authed(&scope, move |c| {
+                let mut req = c.new_request(hyper::Method::Patch, url.clone());
+                req.headers_mut().set(ContentLength(len));
+                req.headers_mut().set_raw("Content-Range", range.clone());
+                req.set_body((*chunk).clone());
+                req
+            }).and_then(|r| match r.status() {
+                hyper::StatusCode::Accepted => r
+                    .headers()
+                    .get::<Location>()
+                    .map(|l| l.to_string())
+                    .ok_or_else(|| Error::from("missing Location header in upload response")),
+                s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+            });
+        Ok(Box::new(fres))
+    }
+
+    /// Finalize a chunked upload session, asserting the final blob digest.
+    pub fn complete_upload(
+        &self,
+        upload_url: &str,
+        digest: &str,
+    ) -> Result<Box<Future<Item = (), Error = Error>>> {
+        let sep = if upload_url.contains('?') { "&" } else { "?" };
+        let url = hyper::Uri::from_str(&format!("{}{}digest={}", upload_url, sep, digest))?;
+        let scope = repo_name_from_upload_path(url.path())
+            .map(|name| format!("repository:{}:push", name))
+            .unwrap_or_default();
+        let fres = self
+            .send_authed(&scope, move |c| c.new_request(hyper::Method::Put, url.clone()))
+            .and_then(|r| match r.status() {
+                hyper::StatusCode::Created => Ok(()),
+                s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+            });
+        Ok(Box::new(fres))
+    }
+
+    /// Delete a blob, by digest.
+    ///
+    /// Per the distribution spec, registries may refuse this with a `405
+    /// Method Not Allowed` if they don't support deletion, which is
+    /// surfaced as `ErrorKind::DeleteUnsupported`.
+    pub fn delete_blob(&self, name: &str, digest: &str) -> Result<Box<Future<Item = (), Error = Error>>> {
+        let url = hyper::Uri::from_str(
+            &format!("{}/v2/{}/blobs/{}", self.base_url, name, digest),
+        )?;
+        let scope = format!("repository:{}:delete", name);
+        let fres = self
+            .send_authed(&scope, move |c| c.new_request(hyper::Method::Delete, url.clone()))
+            .and_then(|r| match r.status() {
+                hyper::StatusCode::Accepted => Ok(()),
+                hyper::StatusCode::MethodNotAllowed => Err(ErrorKind::DeleteUnsupported.into()),
+                hyper::StatusCode::NotFound => Err(ErrorKind::NotFound.into()),
+                s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+            });
+        Ok(Box::new(fres))
+    }
+
+    /// Push a whole blob with a single `PUT` carrying the full content and
+    /// its digest, after opening a session with `start_upload`, for callers
+    /// that already have the full content in memory.
+    pub fn put_blob(
+        &self,
+        name: &str,
+        digest: &str,
+        data: Vec<u8>,
+    ) -> Result<Box<Future<Item = (), Error = Error>>> {
+        let client = self.clone();
+        let digest = digest.to_string();
+        let scope = format!("repository:{}:push", name);
+        let data = Arc::new(data);
+        let fres = self.start_upload(name)?.and_then(move |upload| {
+            let sep = if upload.location.contains('?') { "&" } else { "?" };
+            let url_res = hyper::Uri::from_str(&format!(
+                "{}{}digest={}",
+                upload.location, sep, digest
+            ));
+            futures::future::result(url_res.map_err(Error::from)).and_then(move |url| {
+                client
+                    .send_authed(&scope, move |c| {
+                        let mut req = c.new_request(hyper::Method::Put, url.clone());
+                        req.headers_mut().set(ContentLength(data.len() as u64));
+                        req.headers_mut().set(ContentType::octet_stream());
+                        req.set_body((*data).clone());
+                        req
+                    }).and_then(|r| match r.status() {
+                        hyper::StatusCode::Created => Ok(()),
+                        s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+                    })
+            })
+        });
+        Ok(Box::new(fres))
+    }
+}
+
+/// Pull the repository name out of a blob-upload path of the form
+/// `/v2/<name>/blobs/uploads/<uuid>`, so chunk/finalize requests against
+/// an upload `Location` can be scoped to `repository:<name>:push` instead
+/// of going out unauthenticated.
+fn repo_name_from_upload_path(path: &str) -> Option<&str> {
+    let path = path.trim_start_matches('/');
+    if !path.starts_with("v2/") {
+        return None;
+    }
+    let rest = &path["v2/".len()..];
+    let idx = rest.find("/blobs/uploads/")?;
+    Some(&rest[..idx])
+}
+
+/// Pull the `uuid` query parameter out of an upload session's `Location`,
+/// if present.
+fn uuid_from_location(location: &str) -> String {
+    hyper::Uri::from_str(location)
+        .ok()
+        .and_then(|u| {
+            u.query().and_then(|q| {
+                q.split('&')
+                    .find(|p| p.starts_with("uuid="))
+                    .map(|p| p.trim_start_matches("uuid=").to_string())
+            })
+        }).unwrap_or_default()
+}
+
+/// Compare a computed digest against the one a caller expected.
+fn verify_digest(expected: &str, actual: &str) -> Result<()> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(ErrorKind::DigestMismatch(expected.to_string(), actual.to_string()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{repo_name_from_upload_path, uuid_from_location, verify_digest};
+
+    #[test]
+    fn repo_name_from_upload_path_parses_nested_names() {
+        assert_eq!(
+            repo_name_from_upload_path("/v2/coreos/etcd/blobs/uploads/abc-123"),
+            Some("coreos/etcd")
+        );
+    }
+
+    #[test]
+    fn repo_name_from_upload_path_rejects_non_v2_paths() {
+        assert_eq!(repo_name_from_upload_path("/coreos/etcd/blobs/uploads/abc"), None);
+    }
+
+    #[test]
+    fn uuid_from_location_extracts_query_param() {
+        let uuid = uuid_from_location(
+            "https://registry.example.com/v2/coreos/etcd/blobs/uploads/abc?uuid=abc-123",
+        );
+        assert_eq!(uuid, "abc-123");
+    }
+
+    #[test]
+    fn uuid_from_location_defaults_when_absent() {
+        let uuid = uuid_from_location("https://registry.example.com/v2/coreos/etcd/blobs/uploads/abc");
+        assert_eq!(uuid, "");
+    }
+
+    #[test]
+    fn verify_digest_matches() {
+        assert!(verify_digest("sha256:aaa", "sha256:aaa").is_ok());
+    }
+
+    #[test]
+    fn verify_digest_mismatch_is_an_error() {
+        assert!(verify_digest("sha256:aaa", "sha256:bbb").is_err());
+    }
+}
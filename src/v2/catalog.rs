@@ -0,0 +1,245 @@
+use futures::{Future, Stream};
+use hyper;
+use serde_json;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use super::super::errors::*;
+use super::Client;
+
+/// Default number of repository names requested per `_catalog` page.
+const DEFAULT_CATALOG_PAGE_SIZE: u32 = 100;
+
+#[derive(Debug, Default, Deserialize)]
+struct CatalogResponse {
+    repositories: Vec<String>,
+}
+
+/// A stream of repository names from a registry's `_catalog` endpoint.
+///
+/// Pages are fetched on demand as the stream is polled, following the
+/// `Link: <...>; rel="next"` header the registry returns while more
+/// repositories remain.
+pub struct StreamCatalog<'a> {
+    client: &'a Client,
+    paginate: Option<u32>,
+    buffer: VecDeque<String>,
+    next_url: Option<String>,
+    inflight: Option<Box<Future<Item = (Vec<String>, Option<String>), Error = Error>>>,
+    started: bool,
+}
+
+impl<'a> fmt::Debug for StreamCatalog<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StreamCatalog")
+            .field("client", &self.client)
+            .field("paginate", &self.paginate)
+            .field("buffered", &self.buffer.len())
+            .field("next_url", &self.next_url)
+            .field("started", &self.started)
+            .finish()
+    }
+}
+
+impl<'a> StreamCatalog<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        Self {
+            client,
+            paginate: None,
+            buffer: VecDeque::new(),
+            next_url: None,
+            inflight: None,
+            started: false,
+        }
+    }
+
+    /// Request `n` repository names per page, instead of the default.
+    pub fn paginate(mut self, n: u32) -> Self {
+        self.paginate = Some(n);
+        self
+    }
+
+    fn first_url(&self) -> String {
+        format!(
+            "{}/v2/_catalog?n={}",
+            self.client.base_url,
+            self.paginate.unwrap_or(DEFAULT_CATALOG_PAGE_SIZE)
+        )
+    }
+}
+
+impl<'a> Stream for StreamCatalog<'a> {
+    type Item = String;
+    type Error = Error;
+
+    fn poll(&mut self) -> ::futures::Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(name) = self.buffer.pop_front() {
+                return Ok(::futures::Async::Ready(Some(name)));
+            }
+
+            if self.started && self.next_url.is_none() && self.inflight.is_none() {
+                return Ok(::futures::Async::Ready(None));
+            }
+
+            if self.inflight.is_none() {
+                let url = if self.started {
+                    let next = self.next_url.take().expect("checked above");
+                    if next.starts_with("http://") || next.starts_with("https://") {
+                        next
+                    } else {
+                        format!("{}{}", self.client.base_url, next)
+                    }
+                } else {
+                    self.started = true;
+                    self.first_url()
+                };
+                self.inflight = Some(fetch_catalog_page(self.client, &url)?);
+            }
+
+            let (names, next_url) = match self.inflight.as_mut().unwrap().poll()? {
+                ::futures::Async::Ready(page) => page,
+                ::futures::Async::NotReady => return Ok(::futures::Async::NotReady),
+            };
+            self.inflight = None;
+            self.next_url = next_url;
+            self.buffer.extend(names);
+
+            if self.buffer.is_empty() && self.next_url.is_none() {
+                return Ok(::futures::Async::Ready(None));
+            }
+        }
+    }
+}
+
+fn fetch_catalog_page(
+    client: &Client,
+    url: &str,
+) -> Result<Box<Future<Item = (Vec<String>, Option<String>), Error = Error>>> {
+    let url = hyper::Uri::from_str(url)?;
+    let fres = client
+        .send_authed("registry:catalog:*", move |c| {
+            c.new_request(hyper::Method::Get, url.clone())
+        }).and_then(|r| match r.status() {
+            hyper::StatusCode::Ok => {
+                let next = r
+                    .headers()
+                    .get_raw("Link")
+                    .and_then(|raw| raw.one())
+                    .and_then(|bytes| parse_link_next(&String::from_utf8_lossy(bytes)));
+                Ok((next, r))
+            }
+            s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+        }).and_then(|(next, r)| {
+            r.body()
+                .concat2()
+                .from_err()
+                .map(move |chunk| (next, chunk.to_vec()))
+        }).and_then(|(next, body)| {
+            let parsed: CatalogResponse = serde_json::from_slice(&body)?;
+            Ok((parsed.repositories, next))
+        });
+    Ok(Box::new(fres))
+}
+
+/// Extract the URL from a `Link` response header's `rel="next"` entry, as
+/// returned by `_catalog`/`tags/list` when more pages remain.
+pub(crate) fn parse_link_next(header: &str) -> Option<String> {
+    for part in header.split(',') {
+        let mut segments = part.splitn(2, ';');
+        let url_part = segments.next()?.trim();
+        let rel_part = segments.next().unwrap_or("").trim();
+        if rel_part.replace(' ', "") == "rel=\"next\"" {
+            return Some(url_part.trim_start_matches('<').trim_end_matches('>').to_string());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_link_next, Client, Error, StreamCatalog};
+    use futures::{Async, Future, Stream};
+    use std::cell::Cell;
+
+    #[test]
+    fn parse_link_next_extracts_url() {
+        let header = r#"</v2/_catalog?n=100&last=coreos%2Fetcd>; rel="next""#;
+        assert_eq!(
+            parse_link_next(header),
+            Some("/v2/_catalog?n=100&last=coreos%2Fetcd".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_link_next_returns_none_without_next_rel() {
+        assert_eq!(parse_link_next(r#"</v2/_catalog>; rel="prev""#), None);
+    }
+
+    #[test]
+    fn parse_link_next_returns_none_for_empty_header() {
+        assert_eq!(parse_link_next(""), None);
+    }
+
+    /// A stand-in for a real page fetch: reports `NotReady` once, then
+    /// resolves, so tests can exercise the in-flight-future bookkeeping
+    /// in `poll` without going over the network.
+    struct StubPage {
+        calls: Cell<u32>,
+        page: Option<(Vec<String>, Option<String>)>,
+    }
+
+    impl Future for StubPage {
+        type Item = (Vec<String>, Option<String>);
+        type Error = Error;
+
+        fn poll(&mut self) -> Result<Async<Self::Item>, Self::Error> {
+            let n = self.calls.get();
+            self.calls.set(n + 1);
+            if n == 0 {
+                Ok(Async::NotReady)
+            } else {
+                Ok(Async::Ready(self.page.take().expect("polled after completion")))
+            }
+        }
+    }
+
+    fn dummy_client() -> Client {
+        let core = ::tokio_core::reactor::Core::new().unwrap();
+        Client::configure(&core.handle())
+            .registry("registry.example.com")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn poll_waits_for_an_in_flight_fetch_before_ending() {
+        let client = dummy_client();
+        let mut stream = StreamCatalog::new(&client);
+        stream.started = true;
+        stream.inflight = Some(Box::new(StubPage {
+            calls: Cell::new(0),
+            page: Some((vec!["coreos/etcd".to_string()], None)),
+        }));
+
+        // The fetch is still pending: the stream must not report done yet,
+        // even though `next_url` is `None` at this point.
+        match stream.poll().unwrap() {
+            Async::NotReady => {}
+            other => panic!("expected NotReady while a fetch is in flight, got {:?}", other),
+        }
+
+        // The fetch resolves on the next poll, yielding its one item.
+        match stream.poll().unwrap() {
+            Async::Ready(Some(name)) => assert_eq!(name, "coreos/etcd"),
+            other => panic!("expected Ready(Some(..)), got {:?}", other),
+        }
+
+        // Buffer drained, no next page, no outstanding fetch: now it ends.
+        match stream.poll().unwrap() {
+            Async::Ready(None) => {}
+            other => panic!("expected Ready(None) once exhausted, got {:?}", other),
+        }
+    }
+}
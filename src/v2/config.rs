@@ -0,0 +1,83 @@
+use hyper::client;
+use hyper_rustls;
+use std::cell::RefCell;
+use std::rc::Rc;
+use tokio_core::reactor;
+use ttl_cache::TtlCache;
+
+use super::super::errors::*;
+use super::{Client, TOKEN_CACHE_CAPACITY};
+
+/// Configuration for a `Client`.
+#[derive(Clone, Debug)]
+pub struct Config {
+    handle: reactor::Handle,
+    base_url: String,
+    credentials: Option<(String, String)>,
+    index: String,
+    user_agent: Option<String>,
+}
+
+impl Config {
+    /// Return a default client configuration, to be optionally
+    /// customized before calling `.build()`.
+    pub fn default(handle: &reactor::Handle) -> Self {
+        Self {
+            handle: handle.clone(),
+            base_url: "https://registry-1.docker.io".to_string(),
+            credentials: None,
+            index: "registry-1.docker.io".to_string(),
+            user_agent: Some("dkregistry-rs/0.1.0".to_string()),
+        }
+    }
+
+    /// Set the registry hostname (and optionally port) to connect to.
+    pub fn registry(mut self, registry: &str) -> Self {
+        self.base_url = format!("https://{}", registry);
+        self.index = registry.to_string();
+        self
+    }
+
+    /// Set username/password to use for basic auth during the token
+    /// handshake.
+    pub fn username(mut self, user: Option<String>) -> Self {
+        let pass = self.credentials.map(|(_, p)| p).unwrap_or_default();
+        self.credentials = user.map(|u| (u, pass));
+        self
+    }
+
+    /// Set the password counterpart of `username`.
+    pub fn password(mut self, pass: Option<String>) -> Self {
+        let user = self
+            .credentials
+            .as_ref()
+            .map(|&(ref u, _)| u.clone())
+            .unwrap_or_default();
+        if let Some(p) = pass {
+            self.credentials = Some((user, p));
+        }
+        self
+    }
+
+    /// Set a custom `User-Agent` header.
+    pub fn user_agent(mut self, ua: Option<String>) -> Self {
+        self.user_agent = ua;
+        self
+    }
+
+    /// Build the client.
+    pub fn build(self) -> Result<Client> {
+        let connector = hyper_rustls::HttpsConnector::new(4, &self.handle);
+        let hclient = client::Client::configure().connector(connector).build(&self.handle);
+
+        Ok(Client {
+            base_url: self.base_url,
+            credentials: self.credentials,
+            hclient,
+            index: self.index,
+            user_agent: self.user_agent,
+            token: None,
+            token_cache: Rc::new(RefCell::new(TtlCache::new(TOKEN_CACHE_CAPACITY))),
+        })
+    }
+}
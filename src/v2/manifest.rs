@@ -0,0 +1,224 @@
+use futures::Future;
+use hyper;
+use hyper::header::{qitem, Accept, ContentType};
+use serde_json;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::super::errors::*;
+use super::{Client, FutureManifest};
+
+/// Media type of a single-platform, Docker v2 schema 2 image manifest.
+pub const MEDIA_TYPE_MANIFEST_V2: &str = "application/vnd.docker.distribution.manifest.v2+json";
+/// Media type of a multi-platform Docker manifest list.
+pub const MEDIA_TYPE_MANIFEST_LIST: &str =
+    "application/vnd.docker.distribution.manifest.list.v2+json";
+/// Media type of a single-platform OCI image manifest.
+pub const MEDIA_TYPE_OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+/// Media type of a multi-platform OCI image index.
+pub const MEDIA_TYPE_OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+
+/// The platform a manifest-list/image-index entry was built for.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Platform {
+    pub architecture: String,
+    pub os: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+/// One child entry of a manifest list / OCI image index, pointing at a
+/// concrete per-platform manifest by digest.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ManifestListEntry {
+    pub digest: String,
+    #[serde(rename = "mediaType")]
+    pub media_type: String,
+    pub size: u64,
+    pub platform: Platform,
+}
+
+/// A Docker manifest list, or OCI image index: a thin, multi-platform
+/// wrapper pointing at one concrete manifest per `(os, architecture)`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ManifestList {
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: u32,
+    #[serde(rename = "mediaType")]
+    pub media_type: String,
+    pub manifests: Vec<ManifestListEntry>,
+}
+
+/// An image manifest as fetched from the registry.
+///
+/// Registries may return either a concrete, single-platform manifest, or
+/// a fat manifest (list/index) pointing at several platform-specific
+/// children -- use `resolve_platform` to pick one of those out.
+#[derive(Debug, Clone)]
+pub enum Manifest {
+    V2Schema2(Vec<u8>),
+    ManifestList(ManifestList),
+    OciIndex(ManifestList),
+}
+
+impl Manifest {
+    fn parse(media_type: &str, body: Vec<u8>) -> Result<Self> {
+        match media_type {
+            MEDIA_TYPE_MANIFEST_LIST => Ok(Manifest::ManifestList(serde_json::from_slice(&body)?)),
+            MEDIA_TYPE_OCI_INDEX => Ok(Manifest::OciIndex(serde_json::from_slice(&body)?)),
+            _ => Ok(Manifest::V2Schema2(body)),
+        }
+    }
+}
+
+/// Pick the manifest-list/image-index entry matching the given `os` and
+/// `arch`, so its `digest` can be passed back into `get_manifest`.
+pub fn resolve_platform<'a>(
+    list: &'a ManifestList,
+    os: &str,
+    arch: &str,
+) -> Option<&'a ManifestListEntry> {
+    list.manifests
+        .iter()
+        .find(|m| m.platform.os == os && m.platform.architecture == arch)
+}
+
+impl Client {
+    /// Fetch an image manifest.
+    ///
+    /// The name and reference parameters identify the image. The
+    /// reference may be either a tag or a digest. If the registry has a
+    /// multi-platform manifest list/image index for this reference, it is
+    /// returned as such rather than being resolved to a single platform.
+    pub fn get_manifest(&self, name: &str, reference: &str) -> Result<FutureManifest> {
+        let url = hyper::Uri::from_str(
+            &format!("{}/v2/{}/manifests/{}", self.base_url, name, reference),
+        )?;
+        let scope = format!("repository:{}:pull", name);
+        let accept = [
+            MEDIA_TYPE_MANIFEST_V2,
+            MEDIA_TYPE_MANIFEST_LIST,
+            MEDIA_TYPE_OCI_MANIFEST,
+            MEDIA_TYPE_OCI_INDEX,
+        ].iter()
+            .filter_map(|mt| mt.parse().ok())
+            .map(qitem)
+            .collect::<Vec<_>>();
+
+        let fres = self
+            .send_authed(&scope, move |c| {
+                let mut req = c.new_request(hyper::Method::Get, url.clone());
+                req.headers_mut().set(Accept(accept.clone()));
+                req
+            }).and_then(|r| match r.status() {
+                hyper::StatusCode::Ok => {
+                    let media_type = r
+                        .headers()
+                        .get::<ContentType>()
+                        .map(|ct| ct.to_string())
+                        .unwrap_or_default();
+                    Ok((media_type, r))
+                }
+                s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+            }).and_then(|(media_type, r)| {
+                r.body()
+                    .concat2()
+                    .from_err()
+                    .map(move |chunk| (media_type, chunk.to_vec()))
+            }).and_then(|(media_type, body)| Manifest::parse(&media_type, body));
+        Ok(Box::new(fres))
+    }
+
+    /// Push an image manifest under the given name and reference (tag or
+    /// digest), setting the `Content-Type` to `media_type`.
+    pub fn put_manifest(
+        &self,
+        name: &str,
+        reference: &str,
+        media_type: &str,
+        body: Vec<u8>,
+    ) -> Result<Box<Future<Item = (), Error = Error>>> {
+        let url = hyper::Uri::from_str(
+            &format!("{}/v2/{}/manifests/{}", self.base_url, name, reference),
+        )?;
+        let content_type: hyper::mime::Mime = media_type
+            .parse()
+            .map_err(|_| Error::from(format!("invalid manifest media type: {}", media_type)))?;
+        let body = Arc::new(body);
+        let scope = format!("repository:{}:push", name);
+        let fres = self
+            .send_authed(&scope, move |c| {
+                let mut req = c.new_request(hyper::Method::Put, url.clone());
+                req.headers_mut().set(ContentType(content_type.clone()));
+                req.set_body((*body).clone());
+                req
+            }).and_then(|r| match r.status() {
+                hyper::StatusCode::Created => Ok(()),
+                s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+            });
+        Ok(Box::new(fres))
+    }
+
+    /// Delete a manifest, by digest.
+    ///
+    /// Per the distribution spec, registries may refuse this with a `405
+    /// Method Not Allowed` if they don't support deletion, which is
+    /// surfaced as `ErrorKind::DeleteUnsupported`.
+    pub fn delete_manifest(&self, name: &str, digest: &str) -> Result<Box<Future<Item = (), Error = Error>>> {
+        let url = hyper::Uri::from_str(
+            &format!("{}/v2/{}/manifests/{}", self.base_url, name, digest),
+        )?;
+        let scope = format!("repository:{}:delete", name);
+        let fres = self
+            .send_authed(&scope, move |c| c.new_request(hyper::Method::Delete, url.clone()))
+            .and_then(|r| match r.status() {
+                hyper::StatusCode::Accepted => Ok(()),
+                hyper::StatusCode::MethodNotAllowed => Err(ErrorKind::DeleteUnsupported.into()),
+                hyper::StatusCode::NotFound => Err(ErrorKind::NotFound.into()),
+                s => Err(ErrorKind::UnexpectedHttpStatus(s).into()),
+            });
+        Ok(Box::new(fres))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_platform, ManifestList, ManifestListEntry, Platform};
+
+    fn entry(os: &str, arch: &str, digest: &str) -> ManifestListEntry {
+        ManifestListEntry {
+            digest: digest.to_string(),
+            media_type: super::MEDIA_TYPE_MANIFEST_V2.to_string(),
+            size: 0,
+            platform: Platform {
+                architecture: arch.to_string(),
+                os: os.to_string(),
+                variant: None,
+            },
+        }
+    }
+
+    fn list(manifests: Vec<ManifestListEntry>) -> ManifestList {
+        ManifestList {
+            schema_version: 2,
+            media_type: super::MEDIA_TYPE_MANIFEST_LIST.to_string(),
+            manifests,
+        }
+    }
+
+    #[test]
+    fn resolve_platform_finds_matching_entry() {
+        let list = list(vec![
+            entry("linux", "amd64", "sha256:aaa"),
+            entry("linux", "arm64", "sha256:bbb"),
+        ]);
+        let found = resolve_platform(&list, "linux", "arm64").unwrap();
+        assert_eq!(found.digest, "sha256:bbb");
+    }
+
+    #[test]
+    fn resolve_platform_returns_none_when_absent() {
+        let list = list(vec![entry("linux", "amd64", "sha256:aaa")]);
+        assert!(resolve_platform(&list, "windows", "amd64").is_none());
+    }
+}
@@ -35,8 +35,12 @@ use hyper::{self, client};
 use hyper_rustls;
 use serde_json;
 use tokio_core::reactor;
+use ttl_cache::TtlCache;
 
 use futures::Future;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
 use std::str::FromStr;
 
 mod config;
@@ -54,10 +58,17 @@ mod tags;
 pub use self::tags::StreamTags;
 
 mod blobs;
-pub use self::blobs::FutureBlob;
+pub use self::blobs::{BlobStream, FutureBlob, FutureUpload, Upload};
+
+/// Maximum number of scoped tokens to keep cached at once.
+const TOKEN_CACHE_CAPACITY: usize = 64;
+
+/// Fallback token lifetime, in seconds, used when a token server omits
+/// `expires_in` from its response.
+const DEFAULT_TOKEN_TTL_SECS: u64 = 60;
 
 /// A Client to make outgoing API requests to a registry.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Client {
     base_url: String,
     credentials: Option<(String, String)>,
@@ -65,13 +76,24 @@ pub struct Client {
     index: String,
     user_agent: Option<String>,
     token: Option<String>,
+    token_cache: Rc<RefCell<TtlCache<String, String>>>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("base_url", &self.base_url)
+            .field("index", &self.index)
+            .field("user_agent", &self.user_agent)
+            .finish()
+    }
 }
 
 /// Convenience alias for future boolean result.
 pub type FutureBool = Box<futures::Future<Item = bool, Error = Error>>;
 
-/// Convenience alias for future manifest blob.
-pub type FutureManifest = Box<futures::Future<Item = Vec<u8>, Error = Error>>;
+/// Convenience alias for a future manifest.
+pub type FutureManifest = Box<futures::Future<Item = manifest::Manifest, Error = Error>>;
 
 impl Client {
     pub fn configure(handle: &reactor::Handle) -> Config {
@@ -95,6 +117,84 @@ impl Client {
         return req;
     }
 
+    /// Send a request built by `builder`, transparently performing the
+    /// bearer-token challenge/response handshake on a `401` and retrying
+    /// the original request once a token has been obtained.
+    ///
+    /// `scope` is the resource scope to request (e.g.
+    /// `repository:coreos/etcd:pull`); pass an empty string to fall back
+    /// to whatever scope the registry's own challenge asks for.
+    pub(crate) fn send_authed<F>(
+        &self,
+        scope: &str,
+        builder: F,
+    ) -> Box<Future<Item = client::Response, Error = Error>>
+    where
+        F: Fn(&Client) -> client::Request + 'static,
+    {
+        // A scoped token already cached (and not yet expired) lets us skip
+        // the round-trip through the unauthenticated request entirely.
+        if !scope.is_empty() {
+            if let Some(token) = self.cached_token(scope) {
+                let mut authed = self.clone();
+                authed.token = Some(token);
+                let req = builder(&authed);
+                return Box::new(authed.hclient.request(req).from_err());
+            }
+        }
+
+        let req = builder(self);
+        let client = self.clone();
+        let scope = scope.to_string();
+        let fres = self.hclient.request(req).from_err().and_then(
+            move |res| -> Box<Future<Item = client::Response, Error = Error>> {
+                if res.status() != hyper::StatusCode::Unauthorized {
+                    return Box::new(futures::future::ok(res));
+                }
+
+                let challenge = res
+                    .headers()
+                    .get_raw("WWW-Authenticate")
+                    .and_then(|raw| raw.one())
+                    .ok_or_else(|| Error::from(ErrorKind::MissingAuthChallenge))
+                    .and_then(|bytes| auth::Challenge::parse(&String::from_utf8_lossy(bytes)));
+                let challenge = match challenge {
+                    Ok(c) => c,
+                    Err(e) => return Box::new(futures::future::err(e)),
+                };
+                let scope = if scope.is_empty() {
+                    challenge.scope.clone()
+                } else {
+                    scope.clone()
+                };
+                let fauth = match client.authenticate(&challenge, &scope) {
+                    Ok(f) => f,
+                    Err(e) => return Box::new(futures::future::err(e)),
+                };
+
+                let retry_client = client.clone();
+                Box::new(fauth.and_then(move |token_auth| {
+                    retry_client.cache_token(&token_auth);
+                    let mut authed = retry_client.clone();
+                    authed.token = Some(token_auth.token);
+                    let req = builder(&authed);
+                    authed.hclient.request(req).from_err()
+                }))
+            },
+        );
+        Box::new(fres)
+    }
+
+    /// Stream the names of all repositories visible on this registry.
+    pub fn stream_catalog(&self) -> StreamCatalog {
+        StreamCatalog::new(self)
+    }
+
+    /// Stream the tag names of a given repository.
+    pub fn stream_tags<'a>(&'a self, name: &str) -> StreamTags<'a> {
+        StreamTags::new(self, name)
+    }
+
     pub fn is_v2_supported(&self) -> Result<FutureBool> {
         let api_header = "Docker-Distribution-API-Version";
         let api_version = "registry/2.0";